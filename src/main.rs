@@ -1,6 +1,7 @@
-use std::{path::PathBuf, ffi::OsString, io::{stdin, stdout, Write}};
+use std::{path::{Path, PathBuf}, ffi::OsString, io::{stdin, stdout, Write}, collections::{HashMap, HashSet}};
 use clap::Parser;
 use colored::Colorize;
+use rand::Rng;
 
 type GenericResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
@@ -17,6 +18,31 @@ impl std::fmt::Display for NoFilesRemaining {
 impl std::error::Error for NoFilesRemaining {}
 
 
+#[derive(Debug)]
+struct RenameCollision {
+    conflicts: Vec<(PathBuf, Vec<PathBuf>)>,
+}
+
+impl std::fmt::Display for RenameCollision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Error: Multiple files would be renamed to the same target:")?;
+
+        for (target, sources) in &self.conflicts {
+            let source_list = sources.iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            writeln!(f, "  {} <- {source_list}", target.display())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for RenameCollision {}
+
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -37,6 +63,67 @@ struct Args {
 
     #[arg(short, long, help = "Replaces prefix, rather than deleting it. Can be used with empty prefix input to add a prefix")]
     replace: Option<String>,
+
+    #[arg(short = 'R', long, help = "Recursively descend into subdirectories, computing a separate prefix per directory")]
+    recursive: bool,
+
+    #[arg(long, requires = "recursive", help = "While recursing, skip entries matched by .gitignore, .ignore, and global git excludes")]
+    respect_ignore: bool,
+
+    #[arg(long, conflicts_with_all = ["prefix", "suffix"], requires = "to", help = "Wildcard or regex pattern to match filenames against, mutually exclusive with --prefix")]
+    from: Option<String>,
+
+    #[arg(long, requires = "from", help = "Template for the new filename, using #1, #2, ... for the groups captured by --from")]
+    to: Option<String>,
+
+    #[arg(short = 'S', long, conflicts_with = "prefix", help = "Strip the longest common suffix instead of a prefix")]
+    suffix: bool,
+
+    #[arg(long, requires = "suffix", help = "When stripping a suffix, exclude the file extension from the match and reattach it afterward")]
+    preserve_extension: bool,
+
+    #[arg(long, value_enum, default_value_t = SortOrder::Natural, help = "Order the preview and rename list: natural (numeric-aware), lexical, or none")]
+    sort: SortOrder,
+
+    #[arg(long, help = "Compute the rename plan but don't perform it")]
+    dry_run: bool,
+
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human, help = "Output format for the rename plan: human (colored preview) or json (machine-readable, implies --dry-run)")]
+    format: OutputFormat,
+}
+
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum SortOrder {
+    Natural,
+    Lexical,
+    None,
+}
+
+impl std::fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SortOrder::Natural => write!(f, "natural"),
+            SortOrder::Lexical => write!(f, "lexical"),
+            SortOrder::None => write!(f, "none"),
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    Human,
+    Json,
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Human => write!(f, "human"),
+            OutputFormat::Json => write!(f, "json"),
+        }
+    }
 }
 
 
@@ -65,69 +152,131 @@ impl NamedPath {
 }
 
 
+struct RenameGroup {
+    directory: PathBuf,
+    prefix: String,
+    named_paths: Vec<NamedPath>,
+    new_named_paths: Vec<NamedPath>,
+}
+
+
 fn main() {
     let args = Args::parse();
 
-    let mut named_paths = match get_named_paths(&args) {
+    let named_paths = match get_named_paths(&args) {
         Ok(n_p) => n_p,
         Err(e) => {
             eprintln!("Error getting files: {e}");
-            return;
+            std::process::exit(1);
         }
     };
 
-    let prefix: String = match args.prefix {
-        Some(p) => match vet_named_paths(&p, named_paths) {
-            Ok(vetted) => {
-                named_paths = vetted;
-                p
-            }
+    let mut groups = match &args.from {
+        Some(from) => match build_pattern_groups(&args, from, named_paths) {
+            Ok(g) => g,
             Err(e) => {
                 eprintln!("{e}");
-                return;
-            }
-        }
-        None => match try_find_prefix(&named_paths) {
-            Ok(p_opt) => match p_opt {
-                Some(p) => p,
-                None => {
-                    eprintln!("Couldn't guess a prefix!");
-                    return;
-                }
-            }
-            Err(e) => {
-                eprintln!("Error guessing prefix: {e}");
-                return;
+                std::process::exit(1);
             }
         }
+        None => build_rename_groups(&args, named_paths),
     };
 
-    let prefix_len = prefix.len();
+    if groups.is_empty() {
+        eprintln!("Error: None of the specified files could be affected");
+        std::process::exit(1);
+    }
 
-    let new_named_paths: Vec<NamedPath> = get_new_named_paths(&named_paths, &args.replace, &prefix);
+    for group in groups.iter_mut() {
+        sort_group(group, args.sort);
+    }
 
-    println!("Found prefix: {}", prefix.bold());
-    println!("\nAffected files:");
-    for named_path in named_paths.iter() {
-        let (prefix_name, remainder_name) = named_path.name().split_at(prefix_len);
+    let all_named_paths: Vec<NamedPath> = groups.iter()
+        .flat_map(|g| g.named_paths.iter().cloned())
+        .collect();
+    let all_new_named_paths: Vec<NamedPath> = groups.iter()
+        .flat_map(|g| g.new_named_paths.iter().cloned())
+        .collect();
 
-        println!("{}{remainder_name}", prefix_name.bold().blue());
+    if let Err(e) = detect_collisions(&all_named_paths, &all_new_named_paths) {
+        eprintln!("{e}");
+        std::process::exit(1);
     }
 
-    println!("\nFiles after changes:");
-    for new_named_path in new_named_paths.iter() {
-        let new_prefix_len = match &args.replace {
-            Some(n_p) => n_p.len(),
-            None => 0,
-        };
+    if args.format == OutputFormat::Json {
+        print_json_plan(&groups);
+    } else {
+        let multiple_groups = groups.len() > 1;
+
+        for group in &groups {
+            if multiple_groups {
+                println!("\nDirectory: {}", group.directory.display());
+            }
+
+            if args.from.is_some() {
+                println!("Affected files:");
+                for (named_path, new_named_path) in group.named_paths.iter().zip(group.new_named_paths.iter()) {
+                    println!("{} -> {}", named_path.name(), new_named_path.name().bold().blue());
+                }
+            } else if args.suffix {
+                let suffix_len = group.prefix.len();
+
+                println!("Found suffix: {}", group.prefix.bold());
+                println!("\nAffected files:");
+                for named_path in group.named_paths.iter() {
+                    let (stem, extension) = split_extension(named_path.name(), args.preserve_extension);
+                    let split_point = stem.len().saturating_sub(suffix_len);
+                    let (remainder, matched) = stem.split_at(split_point);
+                    let extension = extension.unwrap_or_default();
+
+                    println!("{remainder}{}{extension}", matched.bold().blue());
+                }
+
+                println!("\nFiles after changes:");
+                for new_named_path in group.new_named_paths.iter() {
+                    let (new_stem, new_extension) = split_extension(new_named_path.name(), args.preserve_extension);
+                    let replace_len = args.replace.as_ref().map(String::len).unwrap_or(0);
+                    let split_point = new_stem.len().saturating_sub(replace_len);
+                    let (remainder, replaced) = new_stem.split_at(split_point);
+                    let new_extension = new_extension.unwrap_or_default();
+
+                    println!("{remainder}{}{new_extension}", replaced.bold().blue());
+                }
+            } else {
+                let prefix_len = group.prefix.len();
 
-        let (prefix_name, remainder_name) = new_named_path.name().split_at(new_prefix_len);
+                println!("Found prefix: {}", group.prefix.bold());
+                println!("\nAffected files:");
+                for named_path in group.named_paths.iter() {
+                    let (prefix_name, remainder_name) = named_path.name().split_at(prefix_len);
 
-        println!("{}{remainder_name}", prefix_name.bold().blue());
+                    println!("{}{remainder_name}", prefix_name.bold().blue());
+                }
+
+                println!("\nFiles after changes:");
+                for new_named_path in group.new_named_paths.iter() {
+                    let new_prefix_len = match &args.replace {
+                        Some(n_p) => n_p.len(),
+                        None => 0,
+                    };
+
+                    let (prefix_name, remainder_name) = new_named_path.name().split_at(new_prefix_len);
+
+                    println!("{}{remainder_name}", prefix_name.bold().blue());
+                }
+            }
+        }
+
+        println!();
     }
 
-    println!();
-    if !&args.skip_confirmation {
+    // JSON output is meant for inspection/piping, not triggering renames in the same
+    // invocation, so it always implies --dry-run.
+    if args.dry_run || args.format == OutputFormat::Json {
+        return;
+    }
+
+    if !args.skip_confirmation {
         loop {
             print!("Rename files? [y/N]: ");
             stdout().flush().unwrap();
@@ -135,7 +284,7 @@ fn main() {
             let mut response = String::new();
             if let Err(e) = stdin().read_line(&mut response) {
                 eprintln!("Failed to read input: {e}");
-                return
+                std::process::exit(1);
             }
 
             match response.trim() {
@@ -146,14 +295,115 @@ fn main() {
         }
     }
 
-    for (old_path, new_path) in named_paths.into_iter().zip(new_named_paths.into_iter()) {
-        if let Err(e) = std::fs::rename(old_path.pathbuf(), new_path.pathbuf()) {
-            eprintln!("Failed to rename {}: {e}", old_path.pathbuf().display());
+    let rename_order = plan_rename_order(&all_named_paths, &all_new_named_paths);
+
+    for (old_path, new_path) in rename_order {
+        if let Err(e) = std::fs::rename(&old_path, &new_path) {
+            eprintln!("Failed to rename {}: {e}", old_path.display());
             continue
         }
     }
 }
 
+// Emits the rename plan as JSON Lines (`{"from", "to", "prefix"}` per record) so it can be
+// piped into other tools without the colored, interactive human preview.
+fn print_json_plan(groups: &[RenameGroup]) {
+    for group in groups {
+        for (named_path, new_named_path) in group.named_paths.iter().zip(group.new_named_paths.iter()) {
+            let record = serde_json::json!({
+                "from": named_path.pathbuf().display().to_string(),
+                "to": new_named_path.pathbuf().display().to_string(),
+                "prefix": group.prefix,
+            });
+
+            println!("{record}");
+        }
+    }
+}
+
+fn detect_collisions(named_paths: &[NamedPath], new_named_paths: &[NamedPath]) -> Result<(), RenameCollision> {
+    let mut targets: HashMap<&PathBuf, Vec<usize>> = HashMap::new();
+
+    for (index, new_named_path) in new_named_paths.iter().enumerate() {
+        targets.entry(new_named_path.pathbuf())
+            .or_default()
+            .push(index);
+    }
+
+    let conflicts: Vec<(PathBuf, Vec<PathBuf>)> = targets.into_iter()
+        .filter(|(_, sources)| sources.len() > 1)
+        .map(|(target, sources)| {
+            let source_paths = sources.into_iter()
+                .map(|index| named_paths[index].pathbuf().clone())
+                .collect();
+
+            (target.clone(), source_paths)
+        })
+        .collect();
+
+    if conflicts.is_empty() {
+        Ok(())
+    } else {
+        Err(RenameCollision { conflicts })
+    }
+}
+
+// Orders the renames so that a file is only moved once whatever currently sits at its
+// target path has already moved out of the way. Chains resolve in dependency order;
+// any remaining cycles are broken by shunting one member through a temporary path.
+// A directory is additionally held back until every still-pending rename nested under
+// it has executed, since renaming it first would invalidate their source paths.
+fn plan_rename_order(named_paths: &[NamedPath], new_named_paths: &[NamedPath]) -> Vec<(PathBuf, PathBuf)> {
+    let mut remaining: Vec<(PathBuf, PathBuf)> = named_paths.iter()
+        .zip(new_named_paths.iter())
+        .map(|(old, new)| (old.pathbuf().clone(), new.pathbuf().clone()))
+        .collect();
+
+    let mut ordered = Vec::with_capacity(remaining.len());
+
+    while !remaining.is_empty() {
+        let sources: HashSet<&PathBuf> = remaining.iter().map(|(old, _)| old).collect();
+
+        let safe_index = remaining.iter().position(|(old, new)| {
+            !sources.contains(new)
+                && !remaining.iter().any(|(other_old, _)| other_old != old && other_old.starts_with(old))
+        });
+
+        if let Some(index) = safe_index {
+            let (old, new) = remaining.remove(index);
+            ordered.push((old, new));
+        } else {
+            // Everything left forms a cycle; break it by moving one member aside first.
+            let (old, new) = remaining.remove(0);
+            let temp = unique_temp_path(&new);
+
+            ordered.push((old, temp.clone()));
+            remaining.push((temp, new));
+        }
+    }
+
+    ordered
+}
+
+fn unique_temp_path(target: &Path) -> PathBuf {
+    loop {
+        let suffix: String = rand::thread_rng()
+            .sample_iter(rand::distributions::Alphanumeric)
+            .take(8)
+            .map(char::from)
+            .collect();
+
+        let mut candidate = target.as_os_str().to_owned();
+        candidate.push(format!(".{suffix}.tmp"));
+        let candidate = PathBuf::from(candidate);
+
+        match candidate.try_exists() {
+            Ok(false) => return candidate,
+            _ => continue,
+        }
+    }
+}
+
 fn get_named_paths(args: &Args) -> GenericResult<Vec<NamedPath>> {
     let named_paths: Vec<NamedPath>;
     if let Some(file_list) = &args.files {
@@ -172,6 +422,8 @@ fn get_named_paths(args: &Args) -> GenericResult<Vec<NamedPath>> {
         }
 
         named_paths = existing;
+    } else if args.recursive {
+        named_paths = walk_directory(&args.source_directory, args.respect_ignore, args.include_directories)?;
     } else {
         let read_dir = std::fs::read_dir(&args.source_directory)?;
 
@@ -208,30 +460,403 @@ fn get_named_paths(args: &Args) -> GenericResult<Vec<NamedPath>> {
     Ok(named_paths)
 }
 
+// Descends into `root` using the `ignore` crate's walker so `.gitignore`, `.ignore`, and
+// global git excludes are honored whenever `respect_ignore` is set. Hidden entries (and
+// therefore `.git`) are always skipped, and directories are only collected as rename
+// targets when `include_directories` is set, so a prefix guess isn't fed entries that
+// aren't actually going to be renamed.
+fn walk_directory(root: &Path, respect_ignore: bool, include_directories: bool) -> GenericResult<Vec<NamedPath>> {
+    let mut builder = ignore::WalkBuilder::new(root);
+    builder.standard_filters(respect_ignore);
+    builder.hidden(true);
+
+    let mut named_paths = Vec::new();
+
+    for entry in builder.build() {
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                eprintln!("Error walking directory: {e}");
+                continue;
+            }
+        };
+
+        if entry.path() == root {
+            continue;
+        }
+
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        if is_dir && !include_directories {
+            continue;
+        }
+
+        match NamedPath::from_pathbuf(entry.into_path()) {
+            Some(p) => named_paths.push(p),
+            None => continue,
+        }
+    }
+
+    Ok(named_paths)
+}
+
+// Groups named paths by their parent directory, preserving first-seen order, so the
+// longest-common-prefix guess is computed per directory rather than across the whole tree.
+fn group_by_parent(named_paths: Vec<NamedPath>) -> Vec<(PathBuf, Vec<NamedPath>)> {
+    let mut groups: Vec<(PathBuf, Vec<NamedPath>)> = Vec::new();
+
+    for named_path in named_paths {
+        let parent = named_path.pathbuf().parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        match groups.iter_mut().find(|(directory, _)| *directory == parent) {
+            Some((_, group)) => group.push(named_path),
+            None => groups.push((parent, vec![named_path])),
+        }
+    }
+
+    groups
+}
+
+// Reorders a group's files (keeping `named_paths` and `new_named_paths` in lockstep) so the
+// preview and the rename loop both iterate in a stable, reviewable order.
+fn sort_group(group: &mut RenameGroup, sort_order: SortOrder) {
+    if matches!(sort_order, SortOrder::None) {
+        return;
+    }
+
+    let mut indices: Vec<usize> = (0..group.named_paths.len()).collect();
+
+    indices.sort_by(|&a, &b| match sort_order {
+        SortOrder::Natural => compare_natural(group.named_paths[a].name(), group.named_paths[b].name()),
+        SortOrder::Lexical => group.named_paths[a].name().cmp(group.named_paths[b].name()),
+        SortOrder::None => std::cmp::Ordering::Equal,
+    });
+
+    group.named_paths = indices.iter().map(|&i| group.named_paths[i].clone()).collect();
+    group.new_named_paths = indices.iter().map(|&i| group.new_named_paths[i].clone()).collect();
+}
+
+// Natural-order comparison: consecutive ASCII digits are grouped into a single chunk and
+// compared numerically (`file2` before `file10`); everything else compares char by char.
+fn compare_natural(a: &str, b: &str) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(&ac), Some(&bc)) if ac.is_ascii_digit() && bc.is_ascii_digit() => {
+                match take_number(&mut a_chars).cmp(&take_number(&mut b_chars)) {
+                    Ordering::Equal => continue,
+                    other => return other,
+                }
+            }
+            (Some(&ac), Some(&bc)) => {
+                a_chars.next();
+                b_chars.next();
+
+                match ac.cmp(&bc) {
+                    Ordering::Equal => continue,
+                    other => return other,
+                }
+            }
+        }
+    }
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> u64 {
+    let mut value: u64 = 0;
+
+    while let Some(&c) = chars.peek() {
+        match c.to_digit(10) {
+            Some(digit) => {
+                value = value.saturating_mul(10).saturating_add(digit as u64);
+                chars.next();
+            }
+            None => break,
+        }
+    }
+
+    value
+}
+
+fn build_rename_groups(args: &Args, named_paths: Vec<NamedPath>) -> Vec<RenameGroup> {
+    let mut groups = Vec::new();
+
+    for (directory, directory_paths) in group_by_parent(named_paths) {
+        // A shared prefix/suffix is only meaningful across at least two files; with one
+        // file there's nothing to distinguish it from, so every guess would be the whole
+        // filename. An explicit --prefix is exempt since the user named it, not us.
+        if args.suffix && directory_paths.len() < 2 {
+            eprintln!("Skipping {}: need at least two files to guess a shared suffix", directory.display());
+            continue;
+        }
+
+        let (segment, vetted) = if args.suffix {
+            match try_find_suffix(&directory_paths, args.preserve_extension) {
+                Ok(Some(s)) => (s, directory_paths),
+                Ok(None) => {
+                    eprintln!("Couldn't guess a suffix in {}!", directory.display());
+                    continue;
+                }
+                Err(e) => {
+                    eprintln!("Error guessing suffix in {}: {e}", directory.display());
+                    continue;
+                }
+            }
+        } else {
+            match &args.prefix {
+                Some(p) => match vet_named_paths(p, directory_paths) {
+                    Ok(vetted) => (p.clone(), vetted),
+                    Err(e) => {
+                        eprintln!("{} in {}", e, directory.display());
+                        continue;
+                    }
+                }
+                None => {
+                    if directory_paths.len() < 2 {
+                        eprintln!("Skipping {}: need at least two files to guess a shared prefix", directory.display());
+                        continue;
+                    }
+
+                    match try_find_prefix(&directory_paths) {
+                        Ok(Some(p)) => (p, directory_paths),
+                        Ok(None) => {
+                            eprintln!("Couldn't guess a prefix in {}!", directory.display());
+                            continue;
+                        }
+                        Err(e) => {
+                            eprintln!("Error guessing prefix in {}: {e}", directory.display());
+                            continue;
+                        }
+                    }
+                }
+            }
+        };
+
+        let replace_str = args.replace.clone().unwrap_or_default();
+        let segment_for_transform = segment.clone();
+        let is_suffix = args.suffix;
+        let preserve_extension = args.preserve_extension;
+
+        let pairs = get_new_named_paths(&vetted, move |name| {
+            if is_suffix {
+                remove_suffix(name, &segment_for_transform, &replace_str, preserve_extension)
+            } else {
+                name.replacen(&segment_for_transform, &replace_str, 1)
+            }
+        });
+
+        if pairs.is_empty() {
+            eprintln!("Error: None of the files in {} could be affected", directory.display());
+            continue;
+        }
+
+        let (named_paths, new_named_paths): (Vec<NamedPath>, Vec<NamedPath>) = pairs.into_iter().unzip();
+
+        groups.push(RenameGroup { directory, prefix: segment, named_paths, new_named_paths });
+    }
+
+    groups
+}
+
+// Splits `name` on its last `.` the way `Path::file_stem`/`extension` do (a leading dot,
+// as in dotfiles, doesn't count), but only when `preserve_extension` is set.
+fn split_extension(name: &str, preserve_extension: bool) -> (String, Option<String>) {
+    if !preserve_extension {
+        return (name.to_string(), None);
+    }
+
+    let path = Path::new(name);
+    match path.extension() {
+        Some(ext) => {
+            let stem = path.file_stem().unwrap().to_string_lossy().to_string();
+            (stem, Some(format!(".{}", ext.to_string_lossy())))
+        }
+        None => (name.to_string(), None),
+    }
+}
+
+fn try_find_suffix(named_paths: &[NamedPath], preserve_extension: bool) -> Result<Option<String>, NoFilesRemaining> {
+    let stems: Vec<String> = named_paths.iter()
+        .map(|p| split_extension(p.name(), preserve_extension).0)
+        .collect();
+
+    let shortest_len = stems.iter()
+        .map(|s| s.chars().count())
+        .min()
+        .unwrap();
+
+    let mut longest_common_suffix: String = String::with_capacity(shortest_len);
+
+    for (char_index, first) in stems[0].chars().rev().enumerate().take(shortest_len) {
+        if !stems.iter().all(|s| s.chars().rev().nth(char_index) == Some(first)) {
+            break
+        }
+
+        longest_common_suffix.insert(0, first)
+    }
+
+    // A suffix that consumes an entire stem would strip some file down to nothing, so
+    // reject it rather than guessing it (this also covers the single-file case, where the
+    // "shared" suffix is trivially the whole name).
+    if longest_common_suffix.is_empty() || longest_common_suffix.chars().count() == shortest_len {
+        return Ok(None)
+    }
+
+    Ok(Some(longest_common_suffix))
+}
+
+fn remove_suffix(name: &str, suffix: &str, replacement: &str, preserve_extension: bool) -> String {
+    let (stem, extension) = split_extension(name, preserve_extension);
+
+    let mut new_stem = stem.clone();
+    if let Some(index) = stem.len().checked_sub(suffix.len()) {
+        if stem[index..] == *suffix {
+            new_stem.truncate(index);
+            new_stem.push_str(replacement);
+        }
+    }
+
+    // An empty stem means the suffix consumed the whole name; report that as an empty
+    // result (rather than reattaching the extension) so the caller can reject it instead
+    // of silently renaming the file down to just its extension, e.g. ".txt".
+    if new_stem.is_empty() {
+        return String::new();
+    }
+
+    match extension {
+        Some(ext) => new_stem + &ext,
+        None => new_stem,
+    }
+}
+
+// Builds the single rename group produced by `--from`/`--to` pattern mode. Unlike prefix
+// mode, there's no per-directory guess to make, so every matching file is renamed as one group.
+fn build_pattern_groups(args: &Args, from: &str, named_paths: Vec<NamedPath>) -> GenericResult<Vec<RenameGroup>> {
+    let pattern = compile_pattern(from)?;
+    let template = args.to.as_deref().unwrap_or_default();
+
+    let vetted = vet_named_paths_by_pattern(&pattern, named_paths)?;
+
+    let pairs = get_new_named_paths(&vetted, |name| {
+        let captures = pattern.captures(name).expect("vet_named_paths_by_pattern guarantees a match");
+        apply_template(template, &captures)
+    });
+
+    if pairs.is_empty() {
+        return Err(Box::new(NoFilesRemaining));
+    }
+
+    let (named_paths, new_named_paths): (Vec<NamedPath>, Vec<NamedPath>) = pairs.into_iter().unzip();
+
+    Ok(vec![RenameGroup {
+        directory: PathBuf::new(),
+        prefix: String::new(),
+        named_paths,
+        new_named_paths,
+    }])
+}
+
+// Translates a `--from` pattern into a regex. A pattern containing a literal `(` is assumed
+// to already be a regex; otherwise `*` and `?` are treated as mmv-style wildcards and turned
+// into capture groups so `#1`, `#2`, ... can reference them from `--to`.
+fn compile_pattern(pattern: &str) -> GenericResult<regex::Regex> {
+    if pattern.contains('(') {
+        return Ok(regex::Regex::new(pattern)?);
+    }
+
+    let mut regex_str = String::from("^");
+
+    for ch in pattern.chars() {
+        match ch {
+            '*' => regex_str.push_str("(.*)"),
+            '?' => regex_str.push_str("(.)"),
+            c => regex_str.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    regex_str.push('$');
+
+    Ok(regex::Regex::new(&regex_str)?)
+}
+
+fn vet_named_paths_by_pattern(pattern: &regex::Regex, named_paths: Vec<NamedPath>) -> Result<Vec<NamedPath>, NoFilesRemaining> {
+    let vetted: Vec<NamedPath> = named_paths.into_iter()
+        .filter(|n_p| pattern.is_match(n_p.name()))
+        .collect();
+
+    if vetted.is_empty() {
+        return Err(NoFilesRemaining)
+    }
+
+    Ok(vetted)
+}
+
+// Substitutes `#1`, `#2`, ... in `template` with the corresponding capture group.
+fn apply_template(template: &str, captures: &regex::Captures) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '#' {
+            result.push(c);
+            continue;
+        }
+
+        let mut digits = String::new();
+        while let Some(&d) = chars.peek() {
+            if !d.is_ascii_digit() {
+                break;
+            }
+
+            digits.push(d);
+            chars.next();
+        }
+
+        match digits.parse::<usize>() {
+            Ok(index) => {
+                if let Some(m) = captures.get(index) {
+                    result.push_str(m.as_str());
+                }
+            }
+            Err(_) => {
+                result.push('#');
+                result.push_str(&digits);
+            }
+        }
+    }
+
+    result
+}
+
 fn try_find_prefix(named_paths: &[NamedPath]) -> Result<Option<String>, NoFilesRemaining> {
     let names: Vec<&str> = named_paths.iter().map(|p| p.name()).collect();
 
-    let max_length = names.iter()
-        .min_by(|&&a, &&b| a.len().cmp(&b.len()))
-        .unwrap()
-        .len();
-
-    let mut longest_common_prefix: String = String::with_capacity(max_length);
+    let shortest_len = names.iter()
+        .map(|n| n.chars().count())
+        .min()
+        .unwrap();
 
-    for index in 0..max_length {
-        let first = names[0].as_bytes()
-            .get(index)
-            .unwrap()
-            .to_owned();
+    let mut longest_common_prefix: String = String::with_capacity(shortest_len);
 
-        if !names.iter().all(|&n| *n.as_bytes().get(index).unwrap() == first) {
+    for (char_index, first) in names[0].chars().enumerate().take(shortest_len) {
+        if !names.iter().all(|n| n.chars().nth(char_index) == Some(first)) {
             break
         }
 
-        longest_common_prefix.push(first as char)
+        longest_common_prefix.push(first)
     }
 
-    if longest_common_prefix.is_empty() {
+    // A prefix that consumes an entire filename would strip some file down to nothing, so
+    // reject it rather than guessing it (this also covers the single-file case, where the
+    // "shared" prefix is trivially the whole name).
+    if longest_common_prefix.is_empty() || longest_common_prefix.chars().count() == shortest_len {
         return Ok(None)
     }
 
@@ -250,22 +875,28 @@ fn vet_named_paths(prefix: &String, named_paths: Vec<NamedPath>) -> Result<Vec<N
     Ok(vetted)
 }
 
-fn get_new_named_paths(named_paths: &Vec<NamedPath>, replace: &Option<String>, prefix: &str) -> Vec<NamedPath> {
-    let mut new_paths: Vec<NamedPath> = Vec::with_capacity(named_paths.len());
-
-    let replace_str = match replace {
-        Some(r) => r.to_owned(),
-        None => String::new(),
-    };
+// Applies `transform` to each named path's file name, pairing the original with its
+// renamed counterpart. A transform that would leave a file with an empty name is skipped
+// (with a warning) rather than panicking or silently renaming onto the parent directory.
+fn get_new_named_paths<F: Fn(&str) -> String>(named_paths: &[NamedPath], transform: F) -> Vec<(NamedPath, NamedPath)> {
+    let mut pairs: Vec<(NamedPath, NamedPath)> = Vec::with_capacity(named_paths.len());
 
     for named_path in named_paths.iter() {
-        let mut new_path = named_path.pathbuf().clone();
-        let new_name = named_path.name().replacen(prefix, &replace_str, 1);
+        let new_name = transform(named_path.name());
 
+        if new_name.is_empty() {
+            eprintln!("Skipping {}: renaming it would leave an empty filename", named_path.pathbuf().display());
+            continue;
+        }
+
+        let mut new_path = named_path.pathbuf().clone();
         new_path.set_file_name(OsString::from(new_name));
 
-        new_paths.push(NamedPath::from_pathbuf(new_path).unwrap())
+        match NamedPath::from_pathbuf(new_path) {
+            Some(new_named_path) => pairs.push((named_path.clone(), new_named_path)),
+            None => eprintln!("Couldn't get filename for renamed {}", named_path.pathbuf().display()),
+        }
     }
 
-    new_paths
+    pairs
 }